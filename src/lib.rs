@@ -10,35 +10,38 @@ use std::io::Result as IOResult;
 use std::io::Error as IOError;
 use std::io::ErrorKind as IOErrorKind;
 use std::cmp;
-use std::collections::{HashMap, BinaryHeap};
+use std::hash::Hash;
+use std::collections::{HashMap, BinaryHeap, VecDeque};
 use bit_vec::BitVec;
 
 /// *HuffmanTree* is a simple tree structure used convert encoded words to decoded words and
 /// vice versa.
 ///
-/// Each leaf of the tree represents a single code word. Their probability is saved as single byte
-/// where 255 represents the highest probability, and 0 means the value does not appear.
+/// The tree is generic over the symbol type `T`, so it is not limited to byte alphabets. Each
+/// leaf of the tree holds a symbol together with its weight (e.g. a raw occurrence count). The
+/// tree is built by repeatedly merging the two lowest-weight nodes, so it only relies on the
+/// relative order of the weights, never on a lossy byte-sized normalization of them.
 ///
-/// You most likely don't want to construct this tree yourself, so look for the 2 methods
-/// for constructing the tree for you.
+/// You most likely don't want to construct this tree yourself, so look for the constructor
+/// methods instead.
 ///
 /// # Examples
 /// ```
 /// extern crate huffman_coding;
 ///
-/// let fake_data = vec![1, 1, 0, 0, 2];
+/// let fake_data = vec![1u8, 1, 0, 0, 2];
 /// let tree = huffman_coding::HuffmanTree::from_data(&fake_data[..]);
 /// let probability = tree.get_byte_prob(1);
 /// assert!(probability.is_some());
 /// assert_eq!(probability.unwrap(), 255);
 /// ```
-#[derive(Eq, Debug)]
-pub enum HuffmanTree {
-    Leaf(u8, u8),
-    Node(Box<HuffmanTree>, Box<HuffmanTree>),
+#[derive(Eq)]
+pub enum HuffmanTree<T> {
+    Leaf(T, u64),
+    Node(Box<HuffmanTree<T>>, Box<HuffmanTree<T>>),
 }
 
-impl Ord for HuffmanTree {
+impl<T> Ord for HuffmanTree<T> where T: Eq {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
         let own_prob = self.get_probability();
         let other_prob = other.get_probability();
@@ -55,14 +58,14 @@ impl Ord for HuffmanTree {
     }
 }
 
-impl PartialOrd for HuffmanTree {
+impl<T> PartialOrd for HuffmanTree<T> where T: Eq {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl PartialEq for HuffmanTree {
-    fn eq(&self, other: &HuffmanTree) -> bool {
+impl<T> PartialEq for HuffmanTree<T> where T: Eq {
+    fn eq(&self, other: &HuffmanTree<T>) -> bool {
         match (self, other) {
             (&HuffmanTree::Leaf(ref x1, ref prob1), &HuffmanTree::Leaf(ref x2, ref prob2)) => {
                 x1 == x2 && prob1 == prob2
@@ -75,37 +78,219 @@ impl PartialEq for HuffmanTree {
     }
 }
 
-impl HuffmanTree {
-    /// Method to read the probability of all 256 possible u8 values from a slice containing 256
-    /// elements.
-    ///
-    /// This method can be used to construct a new tree from a list of probabilities. The first
-    /// element in the slice will be interpreted as the probability of the `0` value appearing, the
-    /// second as the probability of the `1` value, etc.
+impl<T> ::std::fmt::Debug for HuffmanTree<T> where T: ::std::fmt::Debug {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            &HuffmanTree::Leaf(ref item, ref prob) => {
+                f.debug_tuple("Leaf").field(item).field(prob).finish()
+            },
+            &HuffmanTree::Node(ref zero, ref one) => {
+                f.debug_tuple("Node").field(zero).field(one).finish()
+            }
+        }
+    }
+}
+
+/// Scales `count` into the `1..=255` probability range, relative to `max_count`.
+///
+/// A tree built without real weights (e.g. [`try_from_codes`](enum.HuffmanTree.html#method.try_from_codes),
+/// which only has codes to work from) has every leaf at weight `0`, i.e. `max_count == 0`; there
+/// is no meaningful probability to report in that case, so every symbol is given the lowest one
+/// instead of dividing by zero.
+fn normalize_one(count: u64, max_count: u64) -> u8 {
+    if max_count == 0 {
+        return 1;
+    }
+    cmp::max((count * 255 / max_count) as u8, 1)
+}
+
+impl<T> HuffmanTree<T> {
+    fn get_probability(&self) -> u64 {
+        match self {
+            &HuffmanTree::Leaf(_, weight) => weight,
+            &HuffmanTree::Node(ref zero, ref one) => {
+                zero.get_probability() + one.get_probability()
+            }
+        }
+    }
+
+    fn max_leaf_weight(&self) -> u64 {
+        match self {
+            &HuffmanTree::Leaf(_, weight) => weight,
+            &HuffmanTree::Node(ref zero, ref one) => {
+                cmp::max(zero.max_leaf_weight(), one.max_leaf_weight())
+            }
+        }
+    }
+}
+
+impl<T> HuffmanTree<T> where T: Eq + Hash + Clone {
+    /// Reads all of data and constructs a huffman tree according to the provided sample data
     ///
     /// # Examples
     /// ```
     /// extern crate huffman_coding;
+    /// let pseudo_data = vec![0u8, 0, 1, 2, 2];
+    /// let tree = huffman_coding::HuffmanTree::from_data(&pseudo_data[..]);
     ///
-    /// let mut table_data: [u8; 256] = [0; 256];
-    /// table_data[0] = 255;
-    /// table_data[1] = 128;
-    /// table_data[2] = 128;
-    /// let tree = huffman_coding::HuffmanTree::from_table(&table_data[..]);
-    ///
-    /// let test_query = tree.get_byte_prob(1);
+    /// let test_query = tree.get_byte_prob(0);
     /// assert!(test_query.is_some());
-    /// assert_eq!(test_query.unwrap(), 128);
+    /// assert_eq!(test_query.unwrap(), 255);
     /// ```
-    /// # Panics
-    /// If data contains less than 256 elements
-    pub fn from_table(data: &[u8]) -> Self {
-        let mut heap: BinaryHeap<_> = data
-            .iter()
-            .enumerate()
-            .filter(|x| *x.1 > 0)
-            .map(|x| HuffmanTree::Leaf(x.0 as u8, *x.1))
+    pub fn from_data(data: &[T]) -> Self {
+        // Symbols are kept in order of first appearance, so that symbols with equal counts are
+        // always merged in the same order and the same data always yields the same tree.
+        let mut counts: HashMap<T, u64> = HashMap::new();
+        let mut order: Vec<T> = Vec::new();
+        for item in data {
+            let count = counts.entry(item.clone()).or_insert(0);
+            if *count == 0 {
+                order.push(item.clone());
+            }
+            *count += 1;
+        }
+
+        let freqs: Vec<_> = order.into_iter().map(|item| {
+            let count = counts[&item];
+            (item, count)
+        }).collect();
+        HuffmanTree::from_frequencies(&freqs[..])
+    }
+
+    /// Builds a huffman tree directly from exact symbol frequencies, without quantizing them.
+    ///
+    /// Unlike [`from_data`](#method.from_data), which has to count occurrences itself, this
+    /// lets you supply already-aggregated counts (e.g. from a corpus too large to hold in
+    /// memory all at once). The counts are used as-is to decide merge order, so skewed
+    /// distributions keep their full precision instead of being collapsed into a single byte.
+    pub fn from_frequencies(freqs: &[(T, u64)]) -> Self {
+        let leaves = freqs.iter()
+            .cloned()
+            .map(|(item, count)| HuffmanTree::Leaf(item, count))
             .collect();
+        HuffmanTree::from_leaves(leaves)
+    }
+
+    /// Builds a huffman tree whose longest codeword is at most `max_len` bits, using the
+    /// package-merge algorithm.
+    ///
+    /// Plain huffman codes can need codewords much longer than `max_len` for heavily skewed
+    /// frequencies, which some wire formats cap (e.g. a 15-bit maximum). Package-merge finds the
+    /// optimal length-limited code instead: it runs `max_len` "coin packaging" passes over the
+    /// weights, and the number of times each symbol survives into the final selection of the
+    /// `2n - 2` lightest coins gives its code length.
+    ///
+    /// A single-symbol alphabet is given a 1-bit code. Returns an error if `max_len` is too
+    /// small to fit every symbol, i.e. smaller than `ceil(log2(freqs.len()))`, or if any symbol
+    /// has a weight of `0`: the package-merge coin selection relies on weights being strictly
+    /// ordered, and a zero weight can tie with a packaged coin and leave the resulting code
+    /// incomplete. Aggregate such symbols out of `freqs` before calling this (or give them a
+    /// weight of `1`) rather than passing a raw zero count through.
+    pub fn from_frequencies_limited(freqs: &[(T, u64)], max_len: u8) -> IOResult<Self> {
+        let n = freqs.len();
+        if n == 0 {
+            return Err(IOError::new(IOErrorKind::InvalidInput, "from_frequencies_limited: empty alphabet"));
+        }
+        if freqs.iter().any(|&(_, weight)| weight == 0) {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                "from_frequencies_limited: symbol weights must be non-zero",
+            ));
+        }
+        if n == 1 {
+            let (ref symbol, weight) = freqs[0];
+            return Ok(HuffmanTree::duplicate_single_leaf(HuffmanTree::Leaf(symbol.clone(), weight)));
+        }
+
+        let mut min_len = 0u8;
+        while (1usize << min_len) < n {
+            min_len += 1;
+        }
+        if max_len < min_len {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                format!("from_frequencies_limited: max_len {} is too small to fit {} symbols, need at least {}", max_len, n, min_len),
+            ));
+        }
+
+        let lengths = package_merge(freqs, max_len);
+
+        let mut symbols: Vec<(usize, u8)> = lengths.into_iter().enumerate().collect();
+        symbols.sort_by_key(|&(index, len)| (len, index));
+
+        let mut builder: Option<Box<CanonicalNode<T>>> = None;
+        let mut code = BitVec::new();
+        let mut prev_len = 0u8;
+        for (index, len) in symbols {
+            if prev_len > 0 {
+                canonical_code_increment(&mut code)?;
+            }
+            canonical_code_extend(&mut code, len - prev_len);
+            let (ref symbol, weight) = freqs[index];
+            canonical_insert(&mut builder, &code, 0, symbol.clone(), weight)?;
+            prev_len = len;
+        }
+
+        Ok(canonical_into_tree(builder.expect("from_frequencies_limited: empty alphabet"))?)
+    }
+
+    /// Builds a tree from an explicit set of `(symbol, code)` assignments, e.g. one read back
+    /// from a format that transmits raw codewords instead of code lengths or a probability
+    /// table.
+    ///
+    /// Unlike [`from_data`](#method.from_data) and friends, this does not choose the codes
+    /// itself, so it has to validate that they actually form a proper prefix code: no two
+    /// symbols may share a code, no code may be a prefix of another, and the result must not
+    /// leave any branch of the tree without both children. A single-symbol alphabet is accepted
+    /// and given a 1-bit code rather than panicking.
+    pub fn try_from_codes(codes: Vec<(T, BitVec)>) -> Result<Self, HuffmanTreeError> {
+        if codes.is_empty() {
+            return Err(HuffmanTreeError::EmptyAlphabet);
+        }
+        if codes.len() == 1 {
+            let (symbol, _) = codes.into_iter().next().unwrap();
+            return Ok(HuffmanTree::duplicate_single_leaf(HuffmanTree::Leaf(symbol, 0)));
+        }
+
+        let mut root: Option<Box<CodeNode<T>>> = None;
+        for (symbol, code) in codes {
+            code_insert(&mut root, &code, 0, symbol)?;
+        }
+
+        code_into_tree(root.ok_or(HuffmanTreeError::EmptyAlphabet)?)
+    }
+
+    /// Return the probability of the given symbol to appear according to the tree
+    ///
+    /// If this returns None, then the symbol should not appear according to the huffman tree
+    /// If this returns Some, it will be between 255 meaning highest probability, and 1, meaning
+    /// lowest probability
+    ///
+    /// The probability is quantized from the tree's exact symbol weights on the fly, so it is
+    /// only meaningful for transmitting a probability table; the merge order used to build the
+    /// tree itself never goes through this lossy step.
+    pub fn get_prob(&self, symbol: &T) -> Option<u8> {
+        let weight = self.get_weight(symbol)?;
+        let max = self.max_leaf_weight();
+        Some(normalize_one(weight, max))
+    }
+
+    fn get_weight(&self, symbol: &T) -> Option<u64> {
+        match self {
+            &HuffmanTree::Leaf(ref item, weight) if item == symbol => Some(weight),
+            &HuffmanTree::Node(ref zero, ref one) => {
+                zero.get_weight(symbol).or_else(|| one.get_weight(symbol))
+            },
+            _ => None
+        }
+    }
+
+    fn from_leaves(leaves: Vec<HuffmanTree<T>>) -> Self {
+        let mut heap: BinaryHeap<_> = leaves.into_iter().collect();
+
+        if heap.len() == 1 {
+            return HuffmanTree::duplicate_single_leaf(heap.pop().unwrap());
+        }
 
         while heap.len() > 2 {
             let a = heap.pop().unwrap();
@@ -122,31 +307,74 @@ impl HuffmanTree {
         HuffmanTree::Node(Box::new(a), Box::new(b))
     }
 
-    /// Reads all of data and constructs a huffman tree according to the provided sample data
+    /// Turns a lone leaf into a 1-bit code by duplicating it into both branches of a single
+    /// node, so a single-symbol alphabet decodes the same way as any other tree instead of
+    /// requiring a special zero-bit case.
+    fn duplicate_single_leaf(leaf: HuffmanTree<T>) -> HuffmanTree<T> {
+        match leaf {
+            HuffmanTree::Leaf(symbol, weight) => HuffmanTree::Node(
+                Box::new(HuffmanTree::Leaf(symbol.clone(), weight)),
+                Box::new(HuffmanTree::Leaf(symbol, weight)),
+            ),
+            node => node,
+        }
+    }
+
+    fn to_lookup_table(&self) -> HashMap<T, BitVec> {
+        let mut table = HashMap::new();
+        self.to_lookup_table_inner(&mut table, BitVec::new());
+        table
+    }
+
+    fn to_lookup_table_inner(&self, data: &mut HashMap<T, BitVec>, prev: BitVec) {
+        match self {
+            &HuffmanTree::Leaf(ref elem, _) => {
+                data.insert(elem.clone(), prev);
+            },
+            &HuffmanTree::Node(ref zero, ref one) => {
+                let mut zero_branch = prev.clone();
+                zero_branch.push(false);
+                zero.to_lookup_table_inner(data, zero_branch);
+                let mut one_branch = prev;
+                one_branch.push(true);
+                one.to_lookup_table_inner(data, one_branch);
+            }
+        }
+    }
+}
+
+impl HuffmanTree<u8> {
+    /// Method to read the probability of all 256 possible u8 values from a slice containing 256
+    /// elements.
+    ///
+    /// This method can be used to construct a new tree from a list of probabilities. The first
+    /// element in the slice will be interpreted as the probability of the `0` value appearing, the
+    /// second as the probability of the `1` value, etc.
     ///
     /// # Examples
     /// ```
     /// extern crate huffman_coding;
-    /// let pseudo_data = vec![0, 0, 1, 2, 2];
-    /// let tree = huffman_coding::HuffmanTree::from_data(&pseudo_data[..]);
     ///
-    /// let test_query = tree.get_byte_prob(0);
+    /// let mut table_data: [u8; 256] = [0; 256];
+    /// table_data[0] = 255;
+    /// table_data[1] = 128;
+    /// table_data[2] = 128;
+    /// let tree = huffman_coding::HuffmanTree::from_table(&table_data[..]);
+    ///
+    /// let test_query = tree.get_byte_prob(1);
     /// assert!(test_query.is_some());
-    /// assert_eq!(test_query.unwrap(), 255);
+    /// assert_eq!(test_query.unwrap(), 128);
     /// ```
-    pub fn from_data(data: &[u8]) -> Self {
-        let mut probability: [usize; 256] = [0; 256];
-        let mut max = 0;
-        for item in data {
-            probability[*item as usize] += 1;
-
-            if probability[*item as usize] > max {
-                max = probability[*item as usize];
-            }
-        }
-
-        let norm = HuffmanTree::normalize(&probability, max);
-        HuffmanTree::from_table(&norm[..])
+    /// # Panics
+    /// If no element of data is non-zero
+    pub fn from_table(data: &[u8]) -> Self {
+        let leaves = data
+            .iter()
+            .enumerate()
+            .filter(|x| *x.1 > 0)
+            .map(|x| HuffmanTree::Leaf(x.0 as u8, *x.1 as u64))
+            .collect();
+        HuffmanTree::from_leaves(leaves)
     }
 
     /// Convert an existing huffman tree into an array where each element represents the probability
@@ -167,56 +395,298 @@ impl HuffmanTree {
     /// If this returns Some, it will be between 255 meaning highest probability, and 1, meaning
     /// lowest probability
     pub fn get_byte_prob(&self, byte: u8) -> Option<u8> {
+        self.get_prob(&byte)
+    }
+
+    /// Compute, for each possible byte value, the depth of its leaf in the tree, i.e. the
+    /// length in bits of its code word. A length of 0 means the byte does not occur.
+    ///
+    /// Transmitting these lengths instead of a full probability table is both smaller and
+    /// deterministic: [`from_code_lengths`](#method.from_code_lengths) rebuilds the exact same
+    /// canonical tree from them on the decoding side.
+    pub fn to_code_lengths(&self) -> [u8; 256] {
+        let mut lengths = [0u8; 256];
+        self.code_lengths_inner(&mut lengths, 0);
+        lengths
+    }
+
+    fn code_lengths_inner(&self, lengths: &mut [u8; 256], depth: u8) {
         match self {
-            &HuffmanTree::Leaf(item, prob) if item == byte => Some(prob),
+            &HuffmanTree::Leaf(byte, _) => lengths[byte as usize] = depth,
             &HuffmanTree::Node(ref zero, ref one) => {
-                zero.get_byte_prob(byte).or(one.get_byte_prob(byte))
-            },
-            _ => None
+                zero.code_lengths_inner(lengths, depth + 1);
+                one.code_lengths_inner(lengths, depth + 1);
+            }
         }
     }
 
-    fn normalize(data: &[usize], max_elem: usize) -> [u8; 256] {
-        let mut normalized_data: [u8; 256] = [0; 256];
+    /// Rebuild a tree from per-symbol code lengths, assigning canonical codes the same way a
+    /// zstd or HPACK/QPACK decoder would: symbols are ordered ascending by `(length, symbol)`,
+    /// and codes are handed out as `code = (prev_code + 1) << (len - prev_len)`, starting from
+    /// `code = 0`. A length of 0 means the byte does not occur.
+    ///
+    /// This gives every byte the same code length it had in the tree `to_code_lengths` was
+    /// computed from, so only the lengths need to be transmitted instead of a full 256-entry
+    /// probability table. The code words themselves are the canonical ones, which match the
+    /// original tree's only if that tree was itself canonical.
+    ///
+    /// Unlike `to_code_lengths`'s output, `lengths` may come from a peer, so it's validated
+    /// rather than trusted: an under-subscribed set (some bit path left unreachable) fails with
+    /// [`HuffmanTreeError::MissingLeaf`], except for a lone byte of length 1, which is rebuilt
+    /// as the same single-symbol tree `to_code_lengths` reports that length for; and an over-subscribed set (more codewords of some
+    /// length than the length has room for) fails with [`HuffmanTreeError::DuplicateLeaf`] or
+    /// [`HuffmanTreeError::OrphanedLeaf`].
+    pub fn from_code_lengths(lengths: &[u8]) -> Result<Self, HuffmanTreeError> {
+        let mut symbols: Vec<(u8, u8)> = lengths.iter()
+            .enumerate()
+            .filter(|&(_, &len)| len > 0)
+            .map(|(byte, &len)| (byte as u8, len))
+            .collect();
+        symbols.sort_by_key(|&(byte, len)| (len, byte));
 
-        for i in 0..data.len() {
-            if data[i] > 0 {
-                normalized_data[i] = cmp::max((data[i] * 255 / max_elem) as u8, 1);
-            }
+        if symbols.is_empty() {
+            return Err(HuffmanTreeError::EmptyAlphabet);
+        }
+        // A single-symbol tree holds its symbol on both 1-bit paths, and `to_code_lengths`
+        // reports length 1 for it, so that case is rebuilt the same way.
+        if symbols.len() == 1 && symbols[0].1 == 1 {
+            return Ok(HuffmanTree::duplicate_single_leaf(HuffmanTree::Leaf(symbols[0].0, 0)));
         }
-        normalized_data
-    }
 
-    fn get_probability(&self) -> u16 {
-        match self {
-            &HuffmanTree::Leaf(_, prob) => prob as u16,
-            &HuffmanTree::Node(ref zero, ref one) => {
-                zero.get_probability() + one.get_probability()
+        let mut builder: Option<Box<CanonicalNode<u8>>> = None;
+        let mut code = BitVec::new();
+        let mut prev_len = 0u8;
+        for (byte, len) in symbols {
+            if prev_len > 0 {
+                canonical_code_increment(&mut code)?;
             }
+            canonical_code_extend(&mut code, len - prev_len);
+            canonical_insert(&mut builder, &code, 0, byte, 0)?;
+            prev_len = len;
         }
+
+        canonical_into_tree(builder.expect("from_code_lengths: empty alphabet"))
     }
+}
 
-    fn to_lookup_table(&self) -> HashMap<u8, BitVec> {
-        let mut table = HashMap::new();
-        self.to_lookup_table_inner(&mut table, BitVec::new());
-        table
+/// Errors returned when building a [`HuffmanTree`](enum.HuffmanTree.html) from an explicit set
+/// of codes via [`try_from_codes`](enum.HuffmanTree.html#method.try_from_codes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HuffmanTreeError {
+    /// An internal node of the tree was left with only one child, so some bit path is
+    /// unreachable and would never decode to a symbol.
+    MissingLeaf,
+    /// A code is a strict prefix of another code (or vice versa), so the shorter one's symbol
+    /// could never be told apart from the longer one's while decoding.
+    OrphanedLeaf,
+    /// Two symbols were assigned the exact same code.
+    DuplicateLeaf,
+    /// Reserved for parity with `bitstream-io`'s error type; since codes here are supplied as
+    /// a [`BitVec`](https://docs.rs/bit-vec) rather than parsed from raw text, every bit is
+    /// already a valid `0`/`1`, so this variant is never actually produced.
+    InvalidBit,
+    /// No codes were provided at all.
+    EmptyAlphabet,
+}
+
+impl ::std::fmt::Display for HuffmanTreeError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let message = match *self {
+            HuffmanTreeError::MissingLeaf => "tree has an internal node missing a child",
+            HuffmanTreeError::OrphanedLeaf => "a code is a prefix of another code",
+            HuffmanTreeError::DuplicateLeaf => "two symbols share the same code",
+            HuffmanTreeError::InvalidBit => "code contains a bit that is neither 0 nor 1",
+            HuffmanTreeError::EmptyAlphabet => "no codes were provided",
+        };
+        f.write_str(message)
     }
+}
 
-    fn to_lookup_table_inner(&self, data: &mut HashMap<u8, BitVec>, prev: BitVec) {
-        match self {
-            &HuffmanTree::Leaf(ref elem, _) => {
-                data.insert(*elem, prev);
+impl ::std::error::Error for HuffmanTreeError {}
+
+impl From<HuffmanTreeError> for IOError {
+    fn from(err: HuffmanTreeError) -> IOError {
+        IOError::new(IOErrorKind::InvalidData, err)
+    }
+}
+
+/// Intermediate trie built up while validating an explicit set of codes; unlike `HuffmanTree`
+/// it can be grown one code word at a time, since a branch may still be missing its other
+/// child, and insertion can fail instead of panicking on conflicting codes.
+enum CodeNode<T> {
+    Leaf(T),
+    Split(Option<Box<CodeNode<T>>>, Option<Box<CodeNode<T>>>),
+}
+
+fn code_insert<T>(node: &mut Option<Box<CodeNode<T>>>, code: &BitVec, index: usize, symbol: T) -> Result<(), HuffmanTreeError> {
+    if index == code.len() {
+        return match *node {
+            None => {
+                *node = Some(Box::new(CodeNode::Leaf(symbol)));
+                Ok(())
             },
-            &HuffmanTree::Node(ref zero, ref one) => {
-                let mut zero_branch = prev.clone();
-                zero_branch.push(false);
-                zero.to_lookup_table_inner(data, zero_branch);
-                let mut one_branch = prev;
-                one_branch.push(true);
-                one.to_lookup_table_inner(data, one_branch);
+            // A leaf already sitting here is the exact same code claimed twice; a split already
+            // sitting here means some longer code walked past this point earlier, so this code is
+            // a prefix of that one rather than a duplicate of it.
+            Some(ref inner) => match **inner {
+                CodeNode::Leaf(_) => Err(HuffmanTreeError::DuplicateLeaf),
+                CodeNode::Split(..) => Err(HuffmanTreeError::OrphanedLeaf),
+            },
+        };
+    }
+
+    let (mut zero, mut one) = match node.take() {
+        Some(inner) => match *inner {
+            CodeNode::Split(zero, one) => (zero, one),
+            CodeNode::Leaf(_) => return Err(HuffmanTreeError::OrphanedLeaf),
+        },
+        None => (None, None),
+    };
+
+    let result = if code.get(index).unwrap() {
+        code_insert(&mut one, code, index + 1, symbol)
+    } else {
+        code_insert(&mut zero, code, index + 1, symbol)
+    };
+    *node = Some(Box::new(CodeNode::Split(zero, one)));
+    result
+}
+
+fn code_into_tree<T>(node: Box<CodeNode<T>>) -> Result<HuffmanTree<T>, HuffmanTreeError> {
+    match *node {
+        CodeNode::Leaf(symbol) => Ok(HuffmanTree::Leaf(symbol, 0)),
+        CodeNode::Split(zero, one) => {
+            let zero = code_into_tree(zero.ok_or(HuffmanTreeError::MissingLeaf)?)?;
+            let one = code_into_tree(one.ok_or(HuffmanTreeError::MissingLeaf)?)?;
+            Ok(HuffmanTree::Node(Box::new(zero), Box::new(one)))
+        }
+    }
+}
+
+/// Intermediate trie used while assigning canonical codes; unlike `HuffmanTree` it can be
+/// grown one code word at a time, since a branch may still be missing its other child.
+enum CanonicalNode<T> {
+    Leaf(T, u64),
+    Split(Option<Box<CanonicalNode<T>>>, Option<Box<CanonicalNode<T>>>),
+}
+
+/// Increments `code`, treated as a big-endian binary integer of `code.len()` bits, by one.
+///
+/// Canonical code assignment can need codes far longer than any fixed integer width supports
+/// (a heavily skewed alphabet can need codewords dozens of bits long), so the code is tracked as
+/// a `BitVec` that grows with [`canonical_code_extend`](fn.canonical_code_extend.html) instead of
+/// a fixed-width integer that would overflow on a long enough code.
+///
+/// Returns [`HuffmanTreeError::DuplicateLeaf`] if `code` is already all ones: that means the
+/// input lengths are over-subscribed (more codewords of this length were requested than the
+/// length has room for), so the next symbol would collide with one already assigned.
+fn canonical_code_increment(code: &mut BitVec) -> Result<(), HuffmanTreeError> {
+    for i in (0..code.len()).rev() {
+        if !code.get(i).unwrap() {
+            code.set(i, true);
+            return Ok(());
+        }
+        code.set(i, false);
+    }
+    Err(HuffmanTreeError::DuplicateLeaf)
+}
+
+/// Appends `extra` zero bits to the end of `code`, growing it from its current length to
+/// `code.len() + extra` bits.
+fn canonical_code_extend(code: &mut BitVec, extra: u8) {
+    for _ in 0..extra {
+        code.push(false);
+    }
+}
+
+fn canonical_insert<T>(node: &mut Option<Box<CanonicalNode<T>>>, code: &BitVec, index: usize, symbol: T, weight: u64) -> Result<(), HuffmanTreeError> {
+    if index == code.len() {
+        *node = Some(Box::new(CanonicalNode::Leaf(symbol, weight)));
+        return Ok(());
+    }
+
+    let (mut zero, mut one) = match node.take() {
+        Some(inner) => match *inner {
+            CanonicalNode::Split(zero, one) => (zero, one),
+            // A leaf already sitting at this prefix means an earlier, shorter code claimed it,
+            // so this longer code is a prefix conflict rather than a clean extension.
+            CanonicalNode::Leaf(..) => return Err(HuffmanTreeError::OrphanedLeaf),
+        },
+        None => (None, None),
+    };
+
+    let result = if code.get(index).unwrap() {
+        canonical_insert(&mut one, code, index + 1, symbol, weight)
+    } else {
+        canonical_insert(&mut zero, code, index + 1, symbol, weight)
+    };
+    *node = Some(Box::new(CanonicalNode::Split(zero, one)));
+    result
+}
+
+fn canonical_into_tree<T>(node: Box<CanonicalNode<T>>) -> Result<HuffmanTree<T>, HuffmanTreeError> {
+    match *node {
+        CanonicalNode::Leaf(symbol, weight) => Ok(HuffmanTree::Leaf(symbol, weight)),
+        CanonicalNode::Split(zero, one) => {
+            let zero = canonical_into_tree(zero.ok_or(HuffmanTreeError::MissingLeaf)?)?;
+            let one = canonical_into_tree(one.ok_or(HuffmanTreeError::MissingLeaf)?)?;
+            Ok(HuffmanTree::Node(Box::new(zero), Box::new(one)))
+        }
+    }
+}
+
+/// One "coin" in the package-merge algorithm: either an original symbol weight, or a package
+/// formed by merging two coins from the previous level. `symbols` tracks which original symbol
+/// indices are bundled inside, so selecting a coin counts towards all of their code lengths.
+#[derive(Clone)]
+struct Coin {
+    weight: u64,
+    symbols: Vec<usize>,
+}
+
+/// Runs the package-merge algorithm and returns the resulting code length for each symbol,
+/// indexed the same way as the input `freqs` slice.
+///
+/// See [`from_frequencies_limited`](enum.HuffmanTree.html#method.from_frequencies_limited)
+/// for the algorithm outline.
+fn package_merge<T>(freqs: &[(T, u64)], max_len: u8) -> Vec<u8> {
+    let n = freqs.len();
+
+    let mut originals: Vec<Coin> = freqs.iter()
+        .enumerate()
+        .map(|(index, &(_, weight))| Coin { weight: weight, symbols: vec![index] })
+        .collect();
+    originals.sort_by_key(|coin| coin.weight);
+
+    let mut list = originals.clone();
+    let mut counts = vec![0u8; n];
+
+    for level in 1..(max_len as u32 + 1) {
+        if level > 1 {
+            let packaged: Vec<Coin> = list.chunks(2)
+                .filter(|pair| pair.len() == 2)
+                .map(|pair| Coin {
+                    weight: pair[0].weight + pair[1].weight,
+                    symbols: pair[0].symbols.iter().chain(pair[1].symbols.iter()).cloned().collect(),
+                })
+                .collect();
+
+            let mut merged: Vec<Coin> = packaged.into_iter().chain(originals.iter().cloned()).collect();
+            merged.sort_by_key(|coin| coin.weight);
+            list = merged;
+        }
+
+        if level == max_len as u32 {
+            let take = 2 * n - 2;
+            for coin in list.iter().take(take) {
+                for &index in &coin.symbols {
+                    counts[index] += 1;
+                }
             }
         }
     }
+
+    counts
 }
 
 /// *HuffmanWriter* is a Write implementation that writes encoded words to the
@@ -226,7 +696,7 @@ impl HuffmanTree {
 ///
 /// ```
 /// extern crate huffman_coding;
-/// let pseudo_data = vec![0, 0, 1, 2, 2];
+/// let pseudo_data = vec![0u8, 0, 1, 2, 2];
 /// let tree = huffman_coding::HuffmanTree::from_data(&pseudo_data[..]);
 ///
 /// let mut output = Vec::new();
@@ -237,40 +707,97 @@ impl HuffmanTree {
 /// }
 /// assert_eq!(&output[..], [43, 8]);
 /// ```
-pub struct HuffmanWriter<W> where W: Write {
+pub struct HuffmanWriter<W, T> where W: Write, T: Eq + Hash + Clone {
     inner: bitstream::BitWriter<W>,
-    table: HashMap<u8, BitVec>,
+    table: HashMap<T, BitVec>,
 }
 
-impl<W> HuffmanWriter<W> where W: Write {
+impl<W, T> HuffmanWriter<W, T> where W: Write, T: Eq + Hash + Clone {
     /// Construct a new HuffmanWriter using the provided HuffmanTree
-    pub fn new(writer: W, tree: &HuffmanTree) -> Self {
+    pub fn new(writer: W, tree: &HuffmanTree<T>) -> Self {
         HuffmanWriter {
             inner: bitstream::BitWriter::new(writer),
             table: tree.to_lookup_table()
         }
     }
-}
 
-impl<W> Write for HuffmanWriter<W> where W: Write {
-    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+    /// Write a slice of symbols, encoding each one according to the tree this writer was
+    /// constructed with
+    pub fn write_symbols(&mut self, buf: &[T]) -> IOResult<usize> {
         for item in buf {
-            let bits = self.table.get(item).ok_or(IOError::from(IOErrorKind::InvalidData))?;
+            let bits = self.table.get(item).ok_or_else(|| IOError::from(IOErrorKind::InvalidData))?;
             for bit in bits {
                 self.inner.write_bit(bit)?;
             }
         }
         Ok(buf.len())
     }
+}
+
+impl<W> Write for HuffmanWriter<W, u8> where W: Write {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        self.write_symbols(buf)
+    }
 
     fn flush(&mut self) -> IOResult<()> {
         Ok(())
     }
 }
 
-pub struct HuffmanReader<R> where R: Read {
-    inner: bitstream::BitReader<R>,
-    tree: HuffmanTree,
+/// Number of bits decoded per table lookup
+const CHUNK_BITS: u8 = 8;
+
+/// One entry of a compiled decode table: either the chunk's leading bits fully determine a
+/// symbol (`Done`), or they don't and decoding has to carry on into a nested table built for
+/// the remaining subtree (`Continue`).
+enum DecodeEntry<T> {
+    Done(T, u8),
+    Continue(Box<[DecodeEntry<T>]>),
+}
+
+/// Precompute a `2^bits`-entry decode table for `tree`, modeled on `bitstream-io`'s
+/// `ReadHuffmanTree`. Each entry either resolves a symbol within `bits` bits of input, or
+/// points at a nested table to keep descending from there.
+fn build_decode_table<T: Clone>(tree: &HuffmanTree<T>, bits: u8) -> Box<[DecodeEntry<T>]> {
+    let size = 1usize << bits;
+    let mut entries = Vec::with_capacity(size);
+    for chunk in 0..size {
+        let mut state = tree;
+        let mut consumed = 0u8;
+        let entry = loop {
+            if let &HuffmanTree::Leaf(ref symbol, _) = state {
+                break DecodeEntry::Done(symbol.clone(), consumed);
+            }
+            if consumed == bits {
+                break DecodeEntry::Continue(build_decode_table(state, bits));
+            }
+            let bit = (chunk >> (bits - 1 - consumed)) & 1 == 1;
+            state = match state {
+                &HuffmanTree::Node(ref zero, ref one) => if bit { one } else { zero },
+                &HuffmanTree::Leaf(..) => unreachable!(),
+            };
+            consumed += 1;
+        };
+        entries.push(entry);
+    }
+    entries.into_boxed_slice()
+}
+
+/// Pull the next bit from `pending` if we rewound one earlier, otherwise read a fresh one
+fn next_bit<R: Read>(inner: &mut bitstream::BitReader<R>, pending: &mut VecDeque<bool>) -> IOResult<Option<bool>> {
+    if let Some(bit) = pending.pop_front() {
+        Ok(Some(bit))
+    } else {
+        inner.read_bit()
+    }
+}
+
+/// Push the `count` least significant bits of `chunk` back so the next call to `next_bit`
+/// replays them in their original order
+fn rewind_bits(pending: &mut VecDeque<bool>, count: u8, chunk: usize) {
+    for p in (0..count).rev() {
+        pending.push_back((chunk >> p) & 1 == 1);
+    }
 }
 
 /// *HuffmanReader* is a Read implementation that can read encoded words from the inner reader
@@ -278,7 +805,7 @@ pub struct HuffmanReader<R> where R: Read {
 /// # Examples
 /// ```
 /// extern crate huffman_coding;
-/// let pseudo_data = vec![0, 0, 1, 2, 2];
+/// let pseudo_data = vec![0u8, 0, 1, 2, 2];
 /// let tree = huffman_coding::HuffmanTree::from_data(&pseudo_data[..]);
 ///
 /// use std::io::{Read, Cursor};
@@ -289,47 +816,80 @@ pub struct HuffmanReader<R> where R: Read {
 /// assert!(reader.read_exact(&mut buffer[..]).is_ok());
 /// assert_eq!(&buffer[..], &[2, 2, 0, 0, 1]);
 /// ```
-impl<R> HuffmanReader<R> where R: Read {
+pub struct HuffmanReader<R, T> where R: Read, T: Eq + Hash + Clone {
+    inner: bitstream::BitReader<R>,
+    table: Box<[DecodeEntry<T>]>,
+    pending: VecDeque<bool>,
+}
+
+impl<R, T> HuffmanReader<R, T> where R: Read, T: Eq + Hash + Clone {
     /// Construct a new reader, using the provided HuffmanTree for decoding
-    pub fn new(reader: R, tree: HuffmanTree) -> Self {
+    ///
+    /// This builds a compiled lookup table from the tree once up front, so that (for the
+    /// common case of codes no longer than `CHUNK_BITS` bits) decoding a symbol is a single
+    /// array lookup rather than a bit-by-bit walk of the tree.
+    pub fn new(reader: R, tree: HuffmanTree<T>) -> Self {
         HuffmanReader {
             inner: bitstream::BitReader::new(reader),
-            tree: tree,
+            table: build_decode_table(&tree, CHUNK_BITS),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Decode and return a single symbol from the inner reader, or `None` if the stream ended
+    /// cleanly on a code word boundary
+    pub fn read_symbol(&mut self) -> IOResult<Option<T>> {
+        let root_ptr = self.table.as_ptr();
+        let mut table: &[DecodeEntry<T>] = &self.table;
+        loop {
+            let mut chunk = 0usize;
+            let mut got = 0u8;
+            while got < CHUNK_BITS {
+                match next_bit(&mut self.inner, &mut self.pending)? {
+                    Some(bit) => {
+                        chunk = (chunk << 1) | (bit as usize);
+                        got += 1;
+                    },
+                    None => break,
+                }
+            }
+
+            if got == 0 {
+                return if ::std::ptr::eq(table.as_ptr(), root_ptr) {
+                    Ok(None)
+                } else {
+                    Err(IOError::from(IOErrorKind::InvalidData))
+                };
+            }
+
+            let idx = chunk << (CHUNK_BITS - got);
+            match table[idx] {
+                DecodeEntry::Done(ref symbol, bits_consumed) if bits_consumed <= got => {
+                    rewind_bits(&mut self.pending, got - bits_consumed, chunk);
+                    return Ok(Some(symbol.clone()));
+                },
+                DecodeEntry::Continue(ref next) if got == CHUNK_BITS => {
+                    table = next;
+                },
+                _ => return Err(IOError::from(IOErrorKind::InvalidData)),
+            }
         }
     }
 }
 
-impl<R> Read for HuffmanReader<R> where R: Read {
+impl<R> Read for HuffmanReader<R, u8> where R: Read {
     fn read(&mut self, buf: &mut [u8]) -> IOResult<usize> {
         let mut pos = 0;
-        let mut state = &self.tree;
         while pos < buf.len() {
-            let bit_opt = self.inner.read_bit()?;
-            if let Some(bit) = bit_opt {
-                match state {
-                    &HuffmanTree::Leaf(x, _) => {
-                        buf[pos] = x;
-                        pos += 1;
-                        state = &self.tree;
-                    },
-                    &HuffmanTree::Node(ref zero, ref one) => {
-                        state = if bit { one } else { zero };
-                        if let &HuffmanTree::Leaf(x, _) = state {
-                            buf[pos] = x;
-                            pos += 1;
-                            state = &self.tree;
-                        }
-                    }
-                }
-            } else {
-                if &self.tree != state {
-                    return Err(IOError::from(IOErrorKind::InvalidData))
-                } else {
-                    break;
-                }
+            match self.read_symbol()? {
+                Some(x) => {
+                    buf[pos] = x;
+                    pos += 1;
+                },
+                None => break,
             }
         }
-        Ok((pos))
+        Ok(pos)
     }
 }
 
@@ -338,9 +898,23 @@ mod tests {
     use super::*;
     use bit_vec::BitVec;
 
+    // Fibonacci-scaled weights build a maximally unbalanced tree, so the rarest symbols get
+    // codewords far longer than a short, evenly weighted alphabet ever would.
+    fn fibonacci_freqs(count: u16) -> Vec<(u8, u64)> {
+        let mut freqs = Vec::new();
+        let (mut a, mut b) = (1u64, 1u64);
+        for byte in 0..count {
+            freqs.push((byte as u8, a));
+            let next = a + b;
+            a = b;
+            b = next;
+        }
+        freqs
+    }
+
     #[test]
     fn test_tree_builder() {
-        let vec = vec![1, 2, 3, 1, 1, 2];
+        let vec = vec![1u8, 2, 3, 1, 1, 2];
         let tree = HuffmanTree::from_data(&vec[..]);
         let table = tree.to_lookup_table();
 
@@ -350,10 +924,32 @@ mod tests {
         assert_eq!(table[&3u8], BitVec::from_iter(vec![true, true].into_iter()));
     }
 
+    #[test]
+    fn test_from_frequencies_exact() {
+        // A symbol that occurs far more than 255 times as often as another would get rounded
+        // into a single shared bucket by the old byte-normalized `from_table` path. Using exact
+        // frequencies keeps them as distinct, correctly ordered merge weights.
+        //
+        // With weights this far apart the heaviest symbols get strictly shorter codes, while the
+        // two lightest are merged together first and so share the deepest code length.
+        let freqs = vec![(0u8, 1u64), (1u8, 1_000u64), (2u8, 10_000u64), (3u8, 1_000_000u64)];
+        let tree = HuffmanTree::from_frequencies(&freqs[..]);
+
+        assert_eq!(tree.get_weight(&0u8), Some(1));
+        assert_eq!(tree.get_weight(&1u8), Some(1_000));
+        assert_eq!(tree.get_weight(&2u8), Some(10_000));
+        assert_eq!(tree.get_weight(&3u8), Some(1_000_000));
+
+        let table = tree.to_lookup_table();
+        assert!(table[&3u8].len() < table[&2u8].len());
+        assert!(table[&2u8].len() < table[&1u8].len());
+        assert!(table[&1u8].len() == table[&0u8].len());
+    }
+
     #[test]
     fn test_writer() {
         use std::io::Write;
-        let pseudo_data = vec![0, 0, 1, 2, 2];
+        let pseudo_data = vec![0u8, 0, 1, 2, 2];
         let tree = HuffmanTree::from_data(&pseudo_data[..]);
 
         let mut vec = Vec::new();
@@ -386,5 +982,162 @@ mod tests {
         assert!(read_end.is_ok());
         assert_eq!(read_end.unwrap(), 0);
     }
-}
 
+    #[test]
+    fn test_reader_multi_chunk_code() {
+        use std::io::{Read, Write};
+
+        // Codewords longer than `CHUNK_BITS` exercise the decode table's `Continue` /
+        // bit-rewind path, not just a single lookup per symbol.
+        let freqs = fibonacci_freqs(12);
+        let tree = HuffmanTree::from_frequencies(&freqs[..]);
+        assert!(tree.to_lookup_table().values().any(|code| code.len() as u8 > CHUNK_BITS));
+
+        let symbols: Vec<u8> = (0..12u16).map(|byte| byte as u8).collect();
+        let mut encoded = Vec::new();
+        {
+            let mut writer = HuffmanWriter::new(&mut encoded, &tree);
+            assert!(writer.write(&symbols[..]).is_ok());
+        }
+
+        let mut decoded = vec![0u8; symbols.len()];
+        let mut reader = HuffmanReader::new(&encoded[..], tree);
+        assert!(reader.read_exact(&mut decoded[..]).is_ok());
+        assert_eq!(decoded, symbols);
+    }
+
+    #[test]
+    fn test_code_lengths_round_trip_skewed() {
+        // The deepest codeword runs past 32 bits. Canonical code assignment has to track a code
+        // wider than a 32-bit integer, or this round trip panics instead of reproducing the
+        // original lengths.
+        let freqs = fibonacci_freqs(40);
+        let tree = HuffmanTree::from_frequencies(&freqs[..]);
+        let lengths = tree.to_code_lengths();
+        assert!(lengths.iter().cloned().max().unwrap() > 32);
+
+        let rebuilt = HuffmanTree::from_code_lengths(&lengths).unwrap();
+        assert_eq!(rebuilt.to_code_lengths()[..], lengths[..]);
+    }
+
+    #[test]
+    fn test_from_code_lengths_rejects_over_subscribed() {
+        // Three symbols all claiming a 1-bit code ask for more codewords than 1 bit can give
+        // out, so the third one collides with one already assigned.
+        let mut lengths = [0u8; 256];
+        lengths[0] = 1;
+        lengths[1] = 1;
+        lengths[2] = 1;
+        assert!(HuffmanTree::from_code_lengths(&lengths[..]).is_err());
+    }
+
+    #[test]
+    fn test_from_code_lengths_rejects_under_subscribed() {
+        // A single symbol at length 2 leaves the other three codes of that length unclaimed, so
+        // the tree has internal nodes with a missing child instead of a usable code.
+        let mut lengths = [0u8; 256];
+        lengths[0] = 2;
+        assert_eq!(HuffmanTree::from_code_lengths(&lengths[..]).unwrap_err(), HuffmanTreeError::MissingLeaf);
+    }
+
+    #[test]
+    fn test_from_frequencies_limited_caps_code_length() {
+        // These weights would normally need codewords far longer than 8 bits; capping `max_len`
+        // at 8 must still produce a valid, decodable tree for every symbol.
+        let freqs = fibonacci_freqs(40);
+        let tree = HuffmanTree::from_frequencies_limited(&freqs[..], 8).unwrap();
+        let table = tree.to_lookup_table();
+        assert_eq!(table.len(), freqs.len());
+        assert!(table.values().all(|code| code.len() <= 8));
+    }
+
+    #[test]
+    fn test_from_frequencies_limited_rejects_too_small_max_len() {
+        let freqs = vec![(0u8, 1u64), (1u8, 1u64), (2u8, 1u64), (3u8, 1u64)];
+        // 4 symbols need at least 2 bits to distinguish, so a 1-bit cap must be rejected.
+        assert!(HuffmanTree::from_frequencies_limited(&freqs[..], 1).is_err());
+    }
+
+    #[test]
+    fn test_from_frequencies_limited_rejects_zero_weight() {
+        // A zero-weight symbol can tie with a packaged coin during package-merge and leave the
+        // resulting code incomplete, so it's rejected up front instead of failing later while
+        // the incomplete tree is built.
+        let freqs = vec![(0u8, 0u64), (1u8, 1u64)];
+        assert!(HuffmanTree::from_frequencies_limited(&freqs[..], 4).is_err());
+    }
+
+    #[test]
+    fn test_from_frequencies_limited_single_symbol() {
+        let freqs = vec![(42u8, 7u64)];
+        let tree = HuffmanTree::from_frequencies_limited(&freqs[..], 8).unwrap();
+        let table = tree.to_lookup_table();
+        assert_eq!(table[&42u8].len(), 1);
+    }
+
+    #[test]
+    fn test_try_from_codes_single_symbol() {
+        use std::iter::FromIterator;
+        let codes = vec![(42u8, BitVec::from_iter(vec![true].into_iter()))];
+        let tree = HuffmanTree::try_from_codes(codes).unwrap();
+        assert_eq!(tree.to_lookup_table()[&42u8].len(), 1);
+    }
+
+    #[test]
+    fn test_try_from_codes_duplicate() {
+        use std::iter::FromIterator;
+        let codes = vec![
+            (0u8, BitVec::from_iter(vec![false].into_iter())),
+            (1u8, BitVec::from_iter(vec![false].into_iter())),
+        ];
+        assert_eq!(HuffmanTree::try_from_codes(codes).unwrap_err(), HuffmanTreeError::DuplicateLeaf);
+    }
+
+    #[test]
+    fn test_try_from_codes_orphaned_prefix_either_order() {
+        use std::iter::FromIterator;
+        // "0" is a prefix of "00" no matter which of the two is inserted first.
+        let long_then_short = vec![
+            (0u8, BitVec::from_iter(vec![false, false].into_iter())),
+            (1u8, BitVec::from_iter(vec![false].into_iter())),
+        ];
+        assert_eq!(HuffmanTree::try_from_codes(long_then_short).unwrap_err(), HuffmanTreeError::OrphanedLeaf);
+
+        let short_then_long = vec![
+            (1u8, BitVec::from_iter(vec![false].into_iter())),
+            (0u8, BitVec::from_iter(vec![false, false].into_iter())),
+        ];
+        assert_eq!(HuffmanTree::try_from_codes(short_then_long).unwrap_err(), HuffmanTreeError::OrphanedLeaf);
+    }
+
+    #[test]
+    fn test_try_from_codes_missing_leaf() {
+        use std::iter::FromIterator;
+        // "0" and "10" leave the "11" branch unreachable.
+        let codes = vec![
+            (0u8, BitVec::from_iter(vec![false].into_iter())),
+            (1u8, BitVec::from_iter(vec![true, false].into_iter())),
+        ];
+        assert_eq!(HuffmanTree::try_from_codes(codes).unwrap_err(), HuffmanTreeError::MissingLeaf);
+    }
+
+    #[test]
+    fn test_try_from_codes_empty() {
+        let codes: Vec<(u8, BitVec)> = Vec::new();
+        assert_eq!(HuffmanTree::try_from_codes(codes).unwrap_err(), HuffmanTreeError::EmptyAlphabet);
+    }
+
+    #[test]
+    fn test_try_from_codes_get_prob_does_not_divide_by_zero() {
+        // try_from_codes only has codes to work from, so every leaf ends up with weight 0; get_prob
+        // must still return a (lowest) probability instead of dividing by the zero total weight.
+        use std::iter::FromIterator;
+        let codes = vec![
+            (0u8, BitVec::from_iter(vec![false].into_iter())),
+            (1u8, BitVec::from_iter(vec![true].into_iter())),
+        ];
+        let tree = HuffmanTree::try_from_codes(codes).unwrap();
+        assert_eq!(tree.get_prob(&0u8), Some(1));
+        assert_eq!(tree.get_prob(&1u8), Some(1));
+    }
+}